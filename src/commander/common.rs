@@ -1,21 +1,30 @@
 use std::{
-  process::{ Child, ExitStatus },
-  io::{ Result as IoResult, Read }
+  process::{ Child, ChildStderr, ChildStdout, ExitStatus },
+  io::{ Result as IoResult, Read },
+  ops::{ ControlFlow },
+  sync::{ Arc, mpsc },
+  thread,
+  time::{ Duration, Instant }
 };
 use log::{error, info};
 use super::child_ext::{ Communicate };
+use super::errors::{ CommandError };
+use super::metrics::{ ProcessObserver };
 
 
 #[derive(Clone)]
 pub struct RunOpts {
   pub pw: Option<String>,
-  pub showout: bool
+  pub showout: bool,
+  pub timeout: Option<Duration>,
+  pub observer: Option<Arc<dyn ProcessObserver + Send + Sync>>
 }
 
 impl RunOpts {
-  pub fn new(pw: Option<String>, showout: bool) -> Self {
+  pub fn new(pw: Option<String>, showout: bool, timeout: Option<Duration>) -> Self {
     RunOpts {
-      pw, showout
+      pw, showout, timeout,
+      observer: None
     }
   }
 }
@@ -24,7 +33,9 @@ impl Default for RunOpts {
   fn default() -> Self {
     RunOpts {
       pw: None,
-      showout: true
+      showout: true,
+      timeout: None,
+      observer: None
     }
   }
 }
@@ -64,52 +75,177 @@ pub fn read_from<T: Read>(output: &mut T, showout: bool) -> Option<String> {
   Some(out)
 }
 
-/// Given a child process, will run to completion.  Unlike wait_output, this function will get the stdout while the
-/// process is still running.
+/// Reads raw chunks from `output` until it is empty, handing each non-empty read to `sink`.
 ///
-/// It is ideal to run this in a separate thread, otherwise the loop will eat up the thread it is running on
-/// TODO: Right now, we have no way to do anything with the output while it is being recieved.  See if it is possible
-/// to make this a futures::future::Stream.  Alternatively, and perhaps more easily, can add a callback with a signature
-/// of Fn(String) -> ()
-pub fn run(process: &mut Child, opts: RunOpts) -> (Option<ExitStatus>, String) {
-  let mut saved_output = String::new();
-  let mut exit_code: Option<ExitStatus> = None;
+/// Unlike `read_from`, the bytes are never assembled into a `String`, so the caller sees raw
+/// bytes as-is (no silent drop of invalid UTF-8) and can bound memory however it likes. Returning
+/// `ControlFlow::Break(())` from `sink` stops the read loop early.
+pub(crate) fn read_chunks<T: Read>(output: &mut T, sink: &mut dyn FnMut(&[u8]) -> ControlFlow<()>) -> ControlFlow<()> {
+  let mut buffer: [u8; 512] = [0; 512];
+
+  while let Ok(size) = output.read(&mut buffer) {
+    if size == 0 {
+      break;
+    }
+    if let ControlFlow::Break(()) = sink(&buffer[0..size]) {
+      return ControlFlow::Break(());
+    }
+  }
+  ControlFlow::Continue(())
+}
+
+/// Given a child process, will run to completion, handing each chunk of stdout to `sink` as it
+/// arrives instead of accumulating it into an owned `String`. `sink` can stop the stream early by
+/// returning `ControlFlow::Break(())`, in which case the child is killed and `run_stream` returns
+/// `Ok(None)` since its final exit status was never observed.
+///
+/// If `opts.timeout` elapses first, the child is killed the same way and `CommandError::Timeout`
+/// is returned. stdout/stderr are each drained on their own thread (see `spawn_stdout_reader`);
+/// captured stderr is attached to `CommandError::NonZeroExit` on a non-zero exit.
+pub fn run_stream(
+  process: &mut Child,
+  opts: RunOpts,
+  mut sink: impl FnMut(&[u8]) -> ControlFlow<()>
+) -> Result<Option<ExitStatus>, CommandError> {
+  enum Outcome {
+    StoppedEarly,
+    TimedOut(Duration),
+    Exited(ExitStatus),
+    Errored(CommandError),
+  }
+
+  let start = Instant::now();
 
   // Check if we have a password.  If so, look at the stderr, and wait for a prompt
-  send_pw(process, opts.pw).expect("Could not pass input to child");
+  send_pw(process, opts.pw)?;
+
+  // Bounded, so a slow sink applies backpressure all the way back to the reader thread (and from
+  // there to the child's own stdout pipe) instead of letting this hop buffer the child's output
+  // without limit.
+  let (chunk_tx, chunk_rx) = mpsc::sync_channel::<Vec<u8>>(64);
+  let stdout_reader = process.stdout.take().map(|out| spawn_stdout_reader(out, chunk_tx));
+  let stderr_reader = process.stderr.take().map(spawn_stderr_reader);
+
+  let outcome = 'outer: loop {
+    for chunk in chunk_rx.try_iter() {
+      if let ControlFlow::Break(()) = sink(&chunk) {
+        break 'outer Outcome::StoppedEarly;
+      }
+    }
 
-  // The stdout may have closed at any time, so check during our loop.
-  loop {
     match process.try_wait() {
       Ok(None) => {
-        // The take() is required, if it is not used, rustc will complain that process.stdout was moved out.
-        if let Some(mut out) = process.stdout.take() {
-          // Read from the buffer until the stdout has no more available data
-          if let Some(data) = read_from(&mut out, opts.showout) {
-            saved_output.push_str(&data);
+        if let Some(timeout) = opts.timeout {
+          if start.elapsed() > timeout {
+            info!("Command exceeded its timeout, killing child");
+            if let Err(e) = process.kill() {
+              error!("Could not kill timed-out child: {}", e);
+            }
+            // Bounded reap: the kill() above means this wait() should return promptly.
+            if let Err(e) = process.wait() {
+              error!("Could not reap killed child: {}", e);
+            }
+            break 'outer Outcome::TimedOut(start.elapsed());
           }
-
-          // take() replaces value with None, so we need to put it back in
-          process.stdout = Some(out);
         }
       }
       Ok(Some(status)) => {
         info!("Process exited with status {}", status);
-        if let Some(mut out) = process.stdout.take() {
-          if let Some(data) = read_from(&mut out, opts.showout) {
-            saved_output.push_str(&data);
-          }
-        }
-        exit_code = Some(status);
-        break;
+        break 'outer Outcome::Exited(status);
       }
       Err(e) => {
         error!("Error with process: {}", e);
-        break;
+        break 'outer Outcome::Errored(CommandError::from(e));
       }
     }
-    std::thread::sleep(std::time::Duration::from_millis(100));
+    thread::sleep(Duration::from_millis(100));
+  };
+
+  // sink stopping early doesn't mean the child is done -- kill and reap it like the timeout path
+  // above does, instead of leaving it running. This also has to happen before the reader threads
+  // are joined below: a still-running child hasn't closed its stdout, so that join would hang
+  // otherwise.
+  if let Outcome::StoppedEarly = outcome {
+    if let Err(e) = process.kill() {
+      error!("Could not kill child after sink stopped the stream early: {}", e);
+    }
+    if let Err(e) = process.wait() {
+      error!("Could not reap killed child: {}", e);
+    }
+  }
+
+  // The child is done (or we gave up on it). Drain the channel until the stdout reader hangs up --
+  // it may be blocked sending into a full channel, so it can only finish once this end reads --
+  // then let the reader threads finish and decide on a final result. Chunks still buffered go to
+  // `sink` unless it already stopped the stream, in which case they are discarded.
+  let stopped_early = matches!(outcome, Outcome::StoppedEarly);
+  for chunk in chunk_rx.iter() {
+    if !stopped_early {
+      let _ = sink(&chunk);
+    }
+  }
+  if let Some(handle) = stdout_reader {
+    if handle.join().is_err() {
+      error!("stdout reader thread panicked");
+    }
   }
+  let captured_stderr = match stderr_reader {
+    Some(handle) => handle.join().unwrap_or_default(),
+    None => String::new(),
+  };
+
+  match outcome {
+    Outcome::StoppedEarly => Ok(None),
+    Outcome::TimedOut(elapsed) => Err(CommandError::Timeout { elapsed }),
+    Outcome::Exited(status) if status.success() => Ok(Some(status)),
+    Outcome::Exited(status) => Err(CommandError::NonZeroExit { status, stderr: captured_stderr }),
+    Outcome::Errored(e) => Err(e),
+  }
+}
+
+/// Spawns a thread that blocks reading `out` until EOF, handing each chunk to `tx`. Run off the
+/// main `run_stream` loop so that loop is never stuck inside a `read()` call.
+fn spawn_stdout_reader(mut out: ChildStdout, tx: mpsc::SyncSender<Vec<u8>>) -> thread::JoinHandle<()> {
+  thread::spawn(move || {
+    let _ = read_chunks(&mut out, &mut |chunk| {
+      match tx.send(chunk.to_vec()) {
+        Ok(()) => ControlFlow::Continue(()),
+        Err(_) => ControlFlow::Break(()),  // receiver (run_stream) is gone, no point reading further
+      }
+    });
+  })
+}
+
+/// Spawns a thread that blocks reading `err` until EOF, returning everything it captured. Never
+/// printed to the screen; this is purely for attaching diagnostics to a `CommandError::NonZeroExit`.
+fn spawn_stderr_reader(mut err: ChildStderr) -> thread::JoinHandle<String> {
+  thread::spawn(move || read_from(&mut err, false).unwrap_or_default())
+}
+
+/// Given a child process, will run to completion.  Unlike wait_output, this function will get the stdout while the
+/// process is still running.
+///
+/// It is ideal to run this in a separate thread, otherwise the loop will eat up the thread it is running on.
+///
+/// This is a thin wrapper around `run_stream` that collects every chunk into an owned `String`,
+/// kept for callers that want the old all-at-once behavior. Long- or constantly-streaming
+/// processes should call `run_stream` directly instead, since this will keep growing
+/// `saved_output` for as long as the child keeps producing data.
+pub fn run(process: &mut Child, opts: RunOpts) -> Result<(Option<ExitStatus>, String), CommandError> {
+  let showout = opts.showout;
+  let mut saved_output = String::new();
+
+  let status = run_stream(process, opts, |chunk| {
+    // FIXME: invalid UTF-8 is silently dropped here, same as the old read_from behavior. Callers
+    // that need the raw bytes should use run_stream directly.
+    if let Ok(body) = std::str::from_utf8(chunk) {
+      if showout {
+        print!("{}", body);
+      }
+      saved_output.push_str(body);
+    }
+    ControlFlow::Continue(())
+  })?;
 
-  (exit_code, saved_output)
+  Ok((status, saved_output))
 }
\ No newline at end of file
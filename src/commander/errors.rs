@@ -1,51 +1,80 @@
 use std::{error,
-          error::Error,
           fmt,
-          io};
+          io,
+          process::ExitStatus,
+          time::Duration};
 
 // Define our error types. These may be customized for our error handling cases.
 // Now we will be able to write our own errors, defer to an underlying error
 // implementation, or do something in between.
-#[derive(Debug, Clone)]
-pub struct CommandError;
-
-impl CommandError {
-    pub fn new() -> Self {
-        CommandError {}
-    }
+//
+// Each variant carries whatever context a caller needs to tell failures apart, rather than
+// collapsing spawn errors, timeouts, and non-zero exits into one opaque value.
+#[derive(Debug)]
+pub enum CommandError {
+    /// The child process could not be spawned, or an I/O error occurred while talking to it.
+    Spawn(io::Error),
+    /// `RunOpts::timeout` elapsed before the child exited, so it was killed.
+    Timeout { elapsed: Duration },
+    /// The child ran to completion but exited with a non-zero status; `stderr` holds whatever
+    /// it had written there.
+    NonZeroExit { status: ExitStatus, stderr: String },
+    /// The remote command couldn't be reached at all -- the transport itself failed (bad host,
+    /// connection refused, authentication rejected, ...) rather than the remote command running
+    /// and exiting non-zero. `RemoteExecutor` reports this instead of `NonZeroExit` for ssh's
+    /// conventional transport-failure exit status, 255.
+    Transport { status: ExitStatus, stderr: String },
+    /// The thread driving the child panicked instead of returning a result.
+    Join,
 }
 
 /// Generation of an error is completely separate from how it is displayed.
 /// There's no need to be concerned about cluttering complex logic with the display style.
-///
-/// Note that we don't store any extra info about the errors. This means we can't state
-/// which string failed to parse without modifying our types to carry that information.
 impl fmt::Display for CommandError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "error executing sub process")
+        match self {
+            CommandError::Spawn(e) => write!(f, "error executing sub process: {}", e),
+            CommandError::Timeout { elapsed } => write!(f, "command timed out after {:?}", elapsed),
+            CommandError::NonZeroExit { status, stderr } => {
+                if stderr.is_empty() {
+                    write!(f, "command exited with {}", status)
+                } else {
+                    write!(f, "command exited with {}: {}", status, stderr.trim_end())
+                }
+            },
+            CommandError::Transport { status, stderr } => {
+                if stderr.is_empty() {
+                    write!(f, "could not reach remote host (ssh exited with {})", status)
+                } else {
+                    write!(f, "could not reach remote host (ssh exited with {}): {}", status, stderr.trim_end())
+                }
+            },
+            CommandError::Join => write!(f, "thread driving the command panicked"),
+        }
     }
 }
 
 // This is important for other errors to wrap this one.
 impl error::Error for CommandError {
-    fn description(&self) -> &str {
-        "error executing sub process"
-    }
-
-    fn cause(&self) -> Option<&dyn error::Error> {
-        // Generic error, underlying cause isn't tracked.
-        None
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CommandError::Spawn(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
 impl From<CommandError> for io::Error {
     fn from(item: CommandError) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, item.description())
+        match item {
+            CommandError::Spawn(e) => e,
+            other => io::Error::other(other.to_string()),
+        }
     }
 }
 
 impl From<io::Error> for CommandError {
-    fn from(_: io::Error) -> CommandError {
-        CommandError::new()
+    fn from(e: io::Error) -> CommandError {
+        CommandError::Spawn(e)
     }
 }
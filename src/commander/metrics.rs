@@ -0,0 +1,54 @@
+use std::{
+  sync::Arc,
+  time::{ Duration, Instant }
+};
+
+/// Receives start/end notifications for every command instrumented with a `MetricsGuard`.
+///
+/// Implement this to wire command execution into whatever metrics backend you use (StatsD,
+/// Prometheus, a log sink, ...) and hand an `Arc` of it to `RunOpts::observer`.
+pub trait ProcessObserver {
+  fn on_start(&self, cmd: &str);
+  fn on_end(&self, cmd: &str, dur: Duration, completed: bool);
+}
+
+/// RAII guard that reports a command's duration and completion status to an optional
+/// `ProcessObserver` when it is dropped.
+///
+/// Call `disarm()` once the command is known to have completed normally. If the guard is still
+/// armed when it's dropped (timeout, a panic in the reader thread, an early return), `on_end` is
+/// told `completed: false`. Because the report fires from `Drop`, every exit path is covered for
+/// free, without extra bookkeeping at each call site.
+pub struct MetricsGuard {
+  cmd: String,
+  start: Instant,
+  armed: bool,
+  observer: Option<Arc<dyn ProcessObserver + Send + Sync>>
+}
+
+impl MetricsGuard {
+  pub fn new(cmd: String, observer: Option<Arc<dyn ProcessObserver + Send + Sync>>) -> Self {
+    if let Some(obs) = &observer {
+      obs.on_start(&cmd);
+    }
+    MetricsGuard {
+      cmd,
+      start: Instant::now(),
+      armed: true,
+      observer
+    }
+  }
+
+  /// Marks the command as having completed normally, so `Drop` reports `completed: true`.
+  pub fn disarm(&mut self) {
+    self.armed = false;
+  }
+}
+
+impl Drop for MetricsGuard {
+  fn drop(&mut self) {
+    if let Some(obs) = &self.observer {
+      obs.on_end(&self.cmd, self.start.elapsed(), !self.armed);
+    }
+  }
+}
@@ -0,0 +1,4 @@
+pub mod common;
+pub mod errors;
+pub mod child_ext;
+pub mod metrics;
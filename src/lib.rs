@@ -1,22 +1,27 @@
 //! This module will handle the abstraction of running commands, either locally or remotely on a machine.
 pub mod commander;
 
-use commander::{ 
+use commander::{
   errors::{ CommandError },
-  common::{ RunOpts, run },
-  child_ext::{ Communicate }
+  common::{ RunOpts, run, run_stream, read_chunks },
+  child_ext::{ Communicate },
+  metrics::{ MetricsGuard }
 };
 
 use std::{
   { thread },
   thread::{ JoinHandle },
   io::{ Result as IoResult },
-  process::{ Child, Command, ExitStatus },
+  ops::{ ControlFlow },
+  process::{ Child, Command, ExitStatus, Stdio },
+  sync::mpsc::{ self, Receiver },
   time::{ Duration, Instant },
 };
 use log::{info, error};
 use tokio::process::{ Command as CommandAsync, Child as ChildAsync, ChildStdout };
-use tokio::io::{ BufReader };
+use tokio::io::{ BufReader, AsyncWriteExt, AsyncBufReadExt, BufReader as TokioBufReader };
+use tokio::net::{ UnixListener, UnixStream };
+use tokio::sync::{ broadcast, watch };
 
 
 /// Trait for a type that can execute a command either synchronously or async
@@ -39,6 +44,16 @@ pub struct AsyncResult {
   pub child: Option<ChildAsync>
 }
 
+/// Like `CommandResult`, but `output` is a stream handle instead of an owned `String` that keeps
+/// growing for as long as the child produces data. Chunks arrive on `output` as they're read from
+/// the child's stdout, so a caller can tail a long-running or constantly-streaming process
+/// without buffering the whole thing in memory. See `run_stream_thread`.
+pub struct StreamResult {
+  pub status: Option<ExitStatus>,
+  pub output: Receiver<Vec<u8>>,
+  pub child: Option<Child>
+}
+
 impl CommandResult {
   /// Wrapper to call into Child's send()
   pub fn send(subproc: &mut Option<Child>, content: String) -> IoResult<()> {
@@ -57,35 +72,194 @@ impl CommandResult {
   }
 
   /// Waits for the child process to exit
-  /// 
-  /// Useful in conjunction with Executor.run_thread().  This function will block until the process ends, or the timeout
-  /// (in milliseconds expires)
+  ///
+  /// Useful in conjunction with Executor.run_thread().  This function will block until the process ends, or the
+  /// timeout (in milliseconds) expires.  Unlike a plain polling loop, hitting the timeout doesn't just give up and
+  /// drop the child handle: the still-running process is killed and reaped so the caller always gets a definite
+  /// terminal state instead of leaking a live child.
   pub fn wait(&mut self, timeout: u64) -> Option<ExitStatus> {
     let start_time = Instant::now();
     let duration = Duration::from_millis(timeout);
     let mut exit_status: Option<ExitStatus> = None;
 
-    while start_time.elapsed() < duration {
-      if let Some(mut child) = self.child.take() {
-        match child.try_wait() {
+    loop {
+      let mut child = match self.child.take() {
+        Some(child) => child,
+        None => break,
+      };
+
+      match child.try_wait() {
+        Ok(Some(status)) => {
+          self.status = Some(status);
+          exit_status = Some(status);
+          self.child = Some(child);
+          break;
+        },
+        Ok(None) => {
+          if start_time.elapsed() > duration {
+            info!("Timed out waiting for child, killing it");
+            if let Err(e) = child.kill() {
+              error!("Could not kill child process: {}", e);
+            }
+            match child.wait() {
+              Ok(status) => {
+                self.status = Some(status);
+                exit_status = Some(status);
+              },
+              Err(e) => error!("Could not reap killed child: {}", e),
+            }
+            self.child = Some(child);
+            break;
+          }
+          self.child = Some(child);
+        },
+        Err(e) => {
+          error!("Child process encountered error: {}", e);
+          self.child = Some(child);
+          break;
+        }
+      }
+      thread::sleep(Duration::from_millis(200));
+    };
+    exit_status
+  }
+
+  /// Spawns `cmd` and turns it into a multiplexed live session: every chunk of its stdout is
+  /// broadcast to every client connected to the Unix socket at `socket_path`, and whatever a
+  /// client writes back is forwarded to the child's stdin via `Communicate::send`.
+  ///
+  /// Returns once the child exits, after signalling every client task to disconnect and removing
+  /// the socket file. The client tasks are not awaited; each one closes its connection when it
+  /// next observes the shutdown signal.
+  pub async fn serve(cmd: &mut Command, opts: RunOpts, socket_path: &str) -> IoResult<()> {
+    let mut process = cmd.spawn()?;
+    crate::commander::common::send_pw(&mut process, opts.pw.clone())?;
+
+    let (output_tx, _) = broadcast::channel::<Vec<u8>>(256);
+    let (stdin_tx, stdin_rx) = mpsc::channel::<String>();
+    // A plain Notify would lose the shutdown signal if the control thread finishes before the
+    // accept loop below first awaits it; watch latches the last value instead, so a late waiter
+    // still sees it.
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+
+    // A stale socket file from a previous run would make bind() fail with AddrInUse.
+    let _ = std::fs::remove_file(socket_path);
+    let listener = UnixListener::bind(socket_path)?;
+
+    // Stdout is drained on its own thread, same as run_stream -- see spawn_stdout_reader.
+    let reader_tx = output_tx.clone();
+    let stdout_reader = process.stdout.take().map(|mut out| {
+      thread::spawn(move || {
+        let _ = read_chunks(&mut out, &mut |chunk| {
+          let _ = reader_tx.send(chunk.to_vec());
+          ControlFlow::Continue(())
+        });
+      })
+    });
+
+    let timeout = opts.timeout;
+    let start = Instant::now();
+    let control = thread::spawn(move || {
+      loop {
+        match process.try_wait() {
+          Ok(None) => {
+            while let Ok(line) = stdin_rx.try_recv() {
+              if let Err(e) = process.send(line) {
+                error!("Could not forward client input to child: {}", e);
+              }
+            }
+
+            if let Some(timeout) = timeout {
+              if start.elapsed() > timeout {
+                info!("Served command exceeded its timeout, killing child");
+                if let Err(e) = process.kill() {
+                  error!("Could not kill timed-out child: {}", e);
+                }
+                let _ = process.wait();
+                break;
+              }
+            }
+          },
           Ok(Some(status)) => {
-            self.status = Some(status);
-            exit_status = Some(status);
+            info!("Served process exited with status {}", status);
             break;
           },
           Err(e) => {
-            error!("Child process encountered error: {}", e);
+            error!("Served process encountered error: {}", e);
             break;
-          },
-          _ => {
-            
           }
         }
-        self.child = Some(child);
+        thread::sleep(Duration::from_millis(100));
       }
-      thread::sleep(Duration::from_millis(200));
-    };
-    exit_status
+      let _ = shutdown_tx.send(true);
+    });
+
+    loop {
+      tokio::select! {
+        accepted = listener.accept() => {
+          match accepted {
+            Ok((stream, _addr)) => {
+              let client_rx = output_tx.subscribe();
+              let client_stdin_tx = stdin_tx.clone();
+              let client_done = shutdown_rx.clone();
+              tokio::spawn(Self::serve_client(stream, client_rx, client_stdin_tx, client_done));
+            },
+            Err(e) => error!("Could not accept client connection: {}", e),
+          }
+        },
+        _ = shutdown_rx.changed() => break,
+      }
+    }
+
+    if control.join().is_err() {
+      error!("Control thread for served command panicked");
+    }
+    if let Some(handle) = stdout_reader {
+      if handle.join().is_err() {
+        error!("stdout reader thread for served command panicked");
+      }
+    }
+    let _ = std::fs::remove_file(socket_path);
+    Ok(())
+  }
+
+  /// Relays broadcast output to one connected client and forwards its input back to `stdin_tx`,
+  /// until the client disconnects or the served command exits.
+  async fn serve_client(
+    stream: UnixStream,
+    mut output_rx: broadcast::Receiver<Vec<u8>>,
+    stdin_tx: mpsc::Sender<String>,
+    mut done: watch::Receiver<bool>
+  ) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut lines = TokioBufReader::new(read_half).lines();
+
+    loop {
+      tokio::select! {
+        chunk = output_rx.recv() => {
+          match chunk {
+            Ok(bytes) => {
+              if write_half.write_all(&bytes).await.is_err() {
+                break;
+              }
+            },
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+          }
+        },
+        line = lines.next_line() => {
+          match line {
+            Ok(Some(text)) => {
+              if stdin_tx.send(text).is_err() {
+                break;
+              }
+            },
+            _ => break,
+          }
+        },
+        _ = done.changed() => break,
+      }
+    }
   }
 }
 
@@ -94,23 +268,20 @@ impl Executor for Command {
 
   /// Executes a subprocess and waits for it to complete
   fn run(&mut self, opts: RunOpts) -> Result<CommandResult, CommandError> {
-    let thrd_handle = run_thread(self, opts)?;    
-    
+    let thrd_handle = run_thread(self, opts)?;
+
     match thrd_handle.join() {
-      Ok(result) => {
-        match result.status {
-          None => {
-            info!("No exit code for the child");
-          },
-          Some(_stat) => {
-            error!("Exit status is {}", _stat);
-          }
-        }
+      Ok(Ok(result)) => {
+        info!("Exit status is {:?}", result.status);
         Ok(result)
       },
+      Ok(Err(e)) => {
+        error!("Command did not complete successfully: {}", e);
+        Err(e)
+      },
       Err(_) => {
         error!("Could not run process");
-        Err(CommandError::new())
+        Err(CommandError::Join)
       }
     }
   }
@@ -123,7 +294,7 @@ impl Executor for CommandAsync {
     let mut child = self.spawn()
         .expect("failed to spawn command");
 
-    let stdout = child.stdout().take()
+    let stdout = child.stdout.take()
         .expect("child did not have a handle to stdout");
 
     let reader = BufReader::new(stdout);
@@ -136,26 +307,192 @@ impl Executor for CommandAsync {
   }
 }
 
+/// How a `RemoteExecutor` authenticates to its host.
+#[derive(Clone)]
+pub enum SshAuth {
+  /// Rely on ssh's own key-based auth (agent, default identity, `known_hosts`, ...); no password
+  /// is sent over the wire to the child.
+  Key,
+  /// Answer ssh's password prompt with this password.
+  ///
+  /// ssh reads its password prompt from the controlling TTY, not from a piped stdin, so this
+  /// can't be wired through `RunOpts.pw`/`Communicate::send` the way a local command's `sudo -S`
+  /// prompt can. Instead `RemoteExecutor::run` shells out through `sshpass` for this variant, so
+  /// the `sshpass` binary must be installed on the machine running `RemoteExecutor`.
+  Password(String),
+}
+
+/// Runs a command on a remote host over `ssh`, implementing `Executor` so it behaves like running
+/// the same command locally: same `RunOpts`, same `CommandResult`, same `CommandError` variants.
+///
+/// The module doc promises running commands "either locally or remotely on a machine" -- this is
+/// the remote half.
+pub struct RemoteExecutor {
+  pub host: String,
+  pub user: String,
+  pub auth: SshAuth,
+  pub cmd: String,
+  pub args: Vec<String>,
+}
+
+impl RemoteExecutor {
+  /// `auth: SshAuth::Password(_)` pulls in a runtime dependency on the `sshpass` binary being on
+  /// `PATH`; see its doc comment. `SshAuth::Key` has no such requirement.
+  pub fn new(host: String, user: String, auth: SshAuth, cmd: String, args: Vec<String>) -> Self {
+    RemoteExecutor { host, user, auth, cmd, args }
+  }
+}
+
+impl Executor for RemoteExecutor {
+  type ExecResult = CommandResult;
+
+  /// Runs `self.cmd self.args` on `self.host` and waits for it to complete.
+  ///
+  /// This builds `ssh user@host -- cmd args...` (wrapped in `sshpass -e` for
+  /// `SshAuth::Password`, see its doc comment) and hands it to `run_thread`, so remote stdout
+  /// streaming, timeouts, and exit-status handling behave identically to a local `Command`. ssh
+  /// forwards the remote command's exit code, so a non-zero status surfaces as the same
+  /// `CommandError::NonZeroExit { status, stderr }` a local failure would. ssh's conventional exit
+  /// status for a transport failure (255 -- bad host, connection refused, auth rejected, ...) is
+  /// special-cased into `CommandError::Transport` instead, so callers can tell "never reached the
+  /// host" apart from "reached the host but the remote command failed".
+  fn run(&mut self, opts: RunOpts) -> Result<CommandResult, CommandError> {
+    let mut ssh_cmd = match &self.auth {
+      SshAuth::Key => {
+        let mut cmd = Command::new("ssh");
+        cmd.arg(format!("{}@{}", self.user, self.host));
+        cmd
+      },
+      SshAuth::Password(pw) => {
+        // `-e` reads the password from the SSHPASS env var instead of an argv flag, so it never
+        // shows up in a `ps` listing.
+        let mut cmd = Command::new("sshpass");
+        cmd
+          .env("SSHPASS", pw)
+          .arg("-e")
+          .arg("ssh")
+          .arg(format!("{}@{}", self.user, self.host));
+        cmd
+      },
+    };
+    ssh_cmd
+      .arg("--")
+      .arg(&self.cmd)
+      .args(&self.args)
+      .stdout(Stdio::piped())
+      .stdin(Stdio::piped());
+
+    let thrd_handle = match run_thread(&mut ssh_cmd, opts) {
+      Ok(handle) => handle,
+      Err(e) if matches!(self.auth, SshAuth::Password(_)) && e.kind() == std::io::ErrorKind::NotFound => {
+        return Err(CommandError::Spawn(std::io::Error::new(
+          e.kind(),
+          format!("sshpass not found on PATH, required for SshAuth::Password: {}", e)
+        )));
+      },
+      Err(e) => return Err(CommandError::from(e)),
+    };
+
+    match thrd_handle.join() {
+      Ok(Ok(result)) => {
+        info!("Exit status is {:?}", result.status);
+        Ok(result)
+      },
+      Ok(Err(CommandError::NonZeroExit { status, stderr })) if status.code() == Some(255) => {
+        error!("Could not reach {}@{}: {}", self.user, self.host, stderr.trim_end());
+        Err(CommandError::Transport { status, stderr })
+      },
+      Ok(Err(e)) => {
+        error!("Remote command did not complete successfully: {}", e);
+        Err(e)
+      },
+      Err(_) => {
+        error!("Could not run remote process");
+        Err(CommandError::Join)
+      }
+    }
+  }
+}
+
 /// Spawns the subprocess, reads the stdout in a separate thread, and returns the thread handle.
-/// 
-/// The thread itself returns a CommandResult.  If the subprocess was unsuccessful, a CommandResult
-/// is still returned but with no status
-pub fn run_thread(cmd: &mut Command, opts: RunOpts) -> IoResult<JoinHandle<CommandResult>> {
-  let mut process = cmd.spawn()?;
+///
+/// The thread returns `Ok(CommandResult)` when the command exits successfully, or
+/// `Err(CommandError)` describing why it didn't (a spawn/IO failure, a timeout, or a non-zero
+/// exit with its captured stderr) so a caller can `match` on the failure instead of being handed
+/// an empty result.
+///
+/// If `opts.observer` is set, a `MetricsGuard` tracks the command from just before it's spawned
+/// until the reader thread finishes with it, reporting whether it completed normally. Because the
+/// guard reports from `Drop`, a timeout, a panic in the reader thread, or an early return are all
+/// correctly reported as "not completed" without any extra bookkeeping below.
+///
+/// `cmd`'s stderr is forced to `Stdio::piped()` (whatever `cmd` was already configured with is
+/// overridden) so `CommandError::NonZeroExit`'s `stderr` field actually has something in it.
+pub fn run_thread(cmd: &mut Command, opts: RunOpts) -> IoResult<JoinHandle<Result<CommandResult, CommandError>>> {
+  let cmd_name = cmd.get_program().to_string_lossy().into_owned();
+  let mut guard = MetricsGuard::new(cmd_name, opts.observer.clone());
+
+  let mut process = cmd.stderr(Stdio::piped()).spawn()?;
 
   // FIXME: When async stabilizes, use task instead of thread
   let thrd_handle = thread::spawn(move || {
-    let (status, output) = run(&mut process, opts);
-        CommandResult {
+    let result = match run(&mut process, opts) {
+      Ok((status, output)) => {
+        guard.disarm();
+        Ok(CommandResult {
           output,
           status,
           child: Some(process)
-        }
-    }
-  );
+        })
+      },
+      Err(e) => {
+        error!("Command did not complete normally: {}", e);
+        Err(e)
+      }
+    };
+    drop(guard);
+    result
+  });
   Ok(thrd_handle)
 }
 
+/// Spawns the subprocess and streams its stdout chunk-by-chunk through the returned
+/// `StreamResult.output`, while the subprocess itself is read to completion on a separate thread.
+///
+/// Unlike `run_thread`, the caller doesn't have to wait for the command to finish to see output:
+/// chunks show up on the channel as the child produces them, so a long-running or
+/// constantly-streaming process (log tailing, `iostat` in a loop, etc.) can be consumed live
+/// instead of buffering it all into memory first. The channel is bounded, so a slow receiver
+/// applies backpressure to the reader thread rather than letting output pile up unbounded.
+/// Once the child exits (or the receiver is dropped), the sender side is dropped and further
+/// reads from `output` return `Err`, signaling completion.
+///
+/// `cmd`'s stderr is forced to `Stdio::piped()` (whatever `cmd` was already configured with is
+/// overridden) so `CommandError::NonZeroExit`'s `stderr` field actually has something in it.
+pub fn run_stream_thread(cmd: &mut Command, opts: RunOpts) -> IoResult<StreamResult> {
+  let mut process = cmd.stderr(Stdio::piped()).spawn()?;
+  let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(64);
+
+  thread::spawn(move || {
+    if let Err(e) = run_stream(&mut process, opts, |chunk| {
+      match tx.send(chunk.to_vec()) {
+        Ok(()) => ControlFlow::Continue(()),
+        Err(_) => ControlFlow::Break(()),  // receiver dropped, no point reading further
+      }
+    }) {
+      error!("Command did not complete normally: {}", e);
+    }
+  });
+
+  // The child and its final exit status stay with the reader thread for as long as streaming is
+  // in progress; the caller learns the stream is done when `output` closes.
+  Ok(StreamResult {
+    status: None,
+    output: rx,
+    child: None
+  })
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -199,9 +536,16 @@ mod tests {
       // subproc might have returned an io::Error, so match for that
       match subproc {
         Ok(mut process) => {
-          let (exit, output) = run(&mut process, RunOpts::default());
-          saved_output = output;
-          exit_code = exit;
+          match run(&mut process, RunOpts::default()) {
+            Ok((exit, output)) => {
+              saved_output = output;
+              exit_code = exit;
+            },
+            Err(e) => {
+              error!("Command did not complete normally: {}", e);
+              assert!(false);
+            }
+          }
         }
         Err(_) => {
           info!("Could not launch subprocess");
@@ -274,6 +618,28 @@ mod tests {
     }
   }
 
+  // #[test]  Requires a real ssh server reachable as stoner@localhost, so this is opt-in like _test_ssh
+  fn _test_remote_executor() {
+    let mut exec = RemoteExecutor::new(
+      "localhost".to_string(),
+      "stoner".to_string(),
+      SshAuth::Key,
+      "echo".to_string(),
+      vec!["hello".to_string()]
+    );
+
+    match exec.run(RunOpts::default()) {
+      Ok(result) => {
+        println!("Remote output: {}", result.output);
+        assert!(result.status.map_or(false, |s| s.success()));
+      },
+      Err(e) => {
+        println!("Remote command failed: {}", e);
+        assert!(false);
+      }
+    }
+  }
+
   #[test]
   fn test_async() -> Result<(), Box<dyn std::error::Error>> {
     // we will use tokio runtime here, since we canr use the tokio::main macro